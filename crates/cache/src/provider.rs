@@ -0,0 +1,458 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use datafusion::{
+    error::DataFusionError, execution::SendableRecordBatchStream,
+    physical_plan::{memory::MemoryStream, stream::RecordBatchStreamAdapter},
+    sql::TableReference,
+};
+use lru::LruCache;
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter};
+use tokio::sync::RwLock;
+
+/// Where a [`CachedQueryResult`]'s batches actually live.
+#[derive(Debug, Clone)]
+pub enum CachedResultData {
+    /// Held entirely in memory, as collected from the original query stream.
+    InMemory(Arc<Vec<RecordBatch>>),
+    /// Spilled to a temporary Parquet file because the result was too large to keep in memory.
+    Spilled {
+        path: Arc<PathBuf>,
+        /// The file's size on disk, in bytes -- used for the on-disk size budget.
+        disk_size: u64,
+    },
+}
+
+/// The result of a cached query: where its batches live, their schema, and the tables the query
+/// read from (used by [`QueryResultsCacheProvider::invalidate_for_table`]).
+#[derive(Debug, Clone)]
+pub struct CachedQueryResult {
+    pub data: CachedResultData,
+    pub schema: SchemaRef,
+    pub input_tables: Arc<HashSet<TableReference>>,
+}
+
+impl CachedQueryResult {
+    /// An in-memory entry's array memory size, or a spilled entry's on-disk file size.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        match &self.data {
+            CachedResultData::InMemory(records) => records
+                .iter()
+                .map(|batch| batch.get_array_memory_size() as u64)
+                .sum(),
+            CachedResultData::Spilled { disk_size, .. } => *disk_size,
+        }
+    }
+
+    /// Reconstructs a [`SendableRecordBatchStream`] over this result's batches, reading them back
+    /// from disk first if they were spilled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the spilled Parquet file can no longer be read.
+    pub fn into_stream(self) -> Result<SendableRecordBatchStream, DataFusionError> {
+        match self.data {
+            CachedResultData::InMemory(records) => Ok(Box::pin(MemoryStream::try_new(
+                records.as_ref().clone(),
+                self.schema,
+                None,
+            )?)),
+            CachedResultData::Spilled { path, .. } => {
+                let schema = Arc::clone(&self.schema);
+                let stream = async_stream::try_stream! {
+                    let path = Arc::clone(&path);
+                    let batches = tokio::task::spawn_blocking(move || read_spilled_batches(&path))
+                        .await
+                        .map_err(|e| DataFusionError::External(Box::new(e)))??;
+                    for batch in batches {
+                        yield batch;
+                    }
+                };
+                Ok(Box::pin(RecordBatchStreamAdapter::new(
+                    schema,
+                    Box::pin(stream),
+                )))
+            }
+        }
+    }
+}
+
+/// Reads every batch back out of a spilled Parquet file (mirroring the `flightpublisher` tool's
+/// Parquet read path).
+fn read_spilled_batches(path: &PathBuf) -> Result<Vec<RecordBatch>, DataFusionError> {
+    let file = std::fs::File::open(path).map_err(|e| DataFusionError::External(Box::new(e)))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+    let reader = builder
+        .build()
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+    reader
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DataFusionError::External(Box::new(e)))
+}
+
+/// Incrementally writes batches to a temporary Parquet file once a cached query's in-memory
+/// results cross [`QueryResultsCacheProviderConfig::max_size`], so large results still get cached
+/// instead of being dropped entirely.
+pub struct SpillWriter {
+    path: Arc<PathBuf>,
+    writer: ArrowWriter<std::fs::File>,
+    disk_size: u64,
+}
+
+impl SpillWriter {
+    /// Creates a new spill file under `spill_dir` and writes `prior_batches` (the batches already
+    /// collected in memory before the threshold was crossed) into it.
+    pub fn create(
+        spill_dir: &std::path::Path,
+        schema: &SchemaRef,
+        prior_batches: &[RecordBatch],
+    ) -> Result<Self, DataFusionError> {
+        std::fs::create_dir_all(spill_dir).map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let path = Arc::new(spill_dir.join(format!("{}.parquet", uuid::Uuid::new_v4())));
+        let file =
+            std::fs::File::create(path.as_ref()).map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let mut writer = ArrowWriter::try_new(file, Arc::clone(schema), None)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        for batch in prior_batches {
+            writer
+                .write(batch)
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        }
+        Ok(Self {
+            path,
+            writer,
+            disk_size: 0,
+        })
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<(), DataFusionError> {
+        self.writer
+            .write(batch)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        self.disk_size = self.writer.bytes_written() as u64;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.disk_size
+    }
+
+    /// Finalizes the file and returns the [`CachedResultData`] pointing at it.
+    pub fn finish(self) -> Result<CachedResultData, DataFusionError> {
+        let mut writer = self.writer;
+        writer
+            .finish()
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let disk_size = std::fs::metadata(self.path.as_ref())
+            .map(|m| m.len())
+            .unwrap_or(self.disk_size);
+        Ok(CachedResultData::Spilled {
+            path: self.path,
+            disk_size,
+        })
+    }
+
+    /// Abandons this spill, deleting the partially-written file.
+    pub fn abandon(self) {
+        let _ = std::fs::remove_file(self.path.as_ref());
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    #[error("the query results cache is disabled")]
+    Disabled,
+}
+
+/// Configuration for a [`QueryResultsCacheProvider`].
+#[derive(Debug, Clone)]
+pub struct QueryResultsCacheProviderConfig {
+    /// The maximum total in-memory size, in bytes, of cached query results.
+    pub max_size: u64,
+    /// The directory spilled (too-large-for-memory) query results are written to.
+    pub spill_dir: PathBuf,
+    /// The maximum total on-disk size, in bytes, of spilled query results.
+    pub max_disk_size: u64,
+    /// The maximum number of distinct query plans to retain, regardless of size.
+    pub max_entries: NonZeroUsize,
+    /// How long a cached entry remains valid before it is treated as expired, independent of
+    /// invalidation. `None` means entries never expire on time.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for QueryResultsCacheProviderConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 128 * 1024 * 1024,
+            spill_dir: std::env::temp_dir().join("spiceai").join("query-cache"),
+            max_disk_size: 1024 * 1024 * 1024,
+            max_entries: NonZeroUsize::new(1000).unwrap_or(NonZeroUsize::MIN),
+            max_age: Some(Duration::from_secs(10 * 60)),
+        }
+    }
+}
+
+struct CacheEntry {
+    result: CachedQueryResult,
+    cached_at: Instant,
+}
+
+impl CacheEntry {
+    fn is_expired(&self, max_age: Option<Duration>) -> bool {
+        max_age.is_some_and(|max_age| self.cached_at.elapsed() > max_age)
+    }
+}
+
+/// Caches query results keyed by logical-plan hash, and tracks which cached plans depend on which
+/// tables so that a table refresh/write can cheaply invalidate only the entries that read it.
+pub struct QueryResultsCacheProvider {
+    config: QueryResultsCacheProviderConfig,
+    entries: RwLock<LruCache<u64, CacheEntry>>,
+    /// Reverse index: table -> the set of `plan_key`s whose cached result depends on it.
+    table_index: RwLock<HashMap<TableReference, HashSet<u64>>>,
+}
+
+impl QueryResultsCacheProvider {
+    #[must_use]
+    pub fn new(config: QueryResultsCacheProviderConfig) -> Self {
+        Self {
+            entries: RwLock::new(LruCache::new(config.max_entries)),
+            table_index: RwLock::new(HashMap::new()),
+            config,
+        }
+    }
+
+    #[must_use]
+    pub fn max_size(&self) -> u64 {
+        self.config.max_size
+    }
+
+    #[must_use]
+    pub fn max_disk_size(&self) -> u64 {
+        self.config.max_disk_size
+    }
+
+    #[must_use]
+    pub fn spill_dir(&self) -> &std::path::Path {
+        &self.config.spill_dir
+    }
+
+    /// Caches `result` under `plan_key`, evicting the least-recently-used entry(s) if the cache is
+    /// over its size budget afterwards, and updates the table -> `plan_key` reverse index.
+    pub async fn put_key(&self, plan_key: u64, result: CachedQueryResult) -> Result<(), CacheError> {
+        let input_tables = Arc::clone(&result.input_tables);
+
+        {
+            let mut entries = self.entries.write().await;
+            if let Some((evicted_key, evicted_entry)) = entries.push(
+                plan_key,
+                CacheEntry {
+                    result,
+                    cached_at: Instant::now(),
+                },
+            ) {
+                if evicted_key != plan_key {
+                    self.unindex(evicted_key, &evicted_entry.result.input_tables)
+                        .await;
+                }
+            }
+
+            self.evict_over_budget(&mut entries).await;
+        }
+
+        let mut table_index = self.table_index.write().await;
+        for table in input_tables.iter() {
+            table_index.entry(table.clone()).or_default().insert(plan_key);
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub async fn get_key(&self, plan_key: u64) -> Option<CachedQueryResult> {
+        let mut entries = self.entries.write().await;
+        let entry = entries.get(&plan_key)?;
+        if entry.is_expired(self.config.max_age) {
+            let expired = entries.pop(&plan_key)?;
+            drop(entries);
+            remove_spilled_file(&expired.result.data);
+            self.unindex(plan_key, &expired.result.input_tables).await;
+            return None;
+        }
+        Some(entry.result.clone())
+    }
+
+    /// Drops every cached entry that depends on `table`. Cheap (a single reverse-index lookup)
+    /// when no cached entry depends on the table.
+    pub async fn invalidate_for_table(&self, table: &TableReference) {
+        let plan_keys = {
+            let mut table_index = self.table_index.write().await;
+            let Some(plan_keys) = table_index.remove(table) else {
+                return;
+            };
+            plan_keys
+        };
+
+        let mut entries = self.entries.write().await;
+        for plan_key in &plan_keys {
+            if let Some(entry) = entries.pop(plan_key) {
+                drop(entries);
+                remove_spilled_file(&entry.result.data);
+                // Remove the evicted entry from every *other* table's set too, not just `table`'s.
+                self.unindex(*plan_key, &entry.result.input_tables).await;
+                entries = self.entries.write().await;
+            }
+        }
+    }
+
+    /// Removes `plan_key` from every table's dependent set in the reverse index.
+    async fn unindex(&self, plan_key: u64, input_tables: &HashSet<TableReference>) {
+        let mut table_index = self.table_index.write().await;
+        for table in input_tables {
+            if let Some(plan_keys) = table_index.get_mut(table) {
+                plan_keys.remove(&plan_key);
+                if plan_keys.is_empty() {
+                    table_index.remove(table);
+                }
+            }
+        }
+    }
+
+    /// Evicts least-recently-used entries until both the in-memory and on-disk (spilled) size
+    /// budgets are satisfied. In-memory and spilled entries share one LRU order, so an eviction
+    /// pass triggered by one budget can end up reclaiming an entry of the other kind -- that's
+    /// fine, since both still just free up space and stay consistent with the reverse index.
+    async fn evict_over_budget(&self, entries: &mut LruCache<u64, CacheEntry>) {
+        let (mut memory_size, mut disk_size) = entries.iter().fold(
+            (0u64, 0u64),
+            |(memory, disk), (_, entry)| match &entry.result.data {
+                CachedResultData::InMemory(_) => (memory + entry.result.size(), disk),
+                CachedResultData::Spilled { .. } => (memory, disk + entry.result.size()),
+            },
+        );
+
+        while memory_size > self.config.max_size || disk_size > self.config.max_disk_size {
+            let Some((evicted_key, evicted_entry)) = entries.pop_lru() else {
+                break;
+            };
+            match &evicted_entry.result.data {
+                CachedResultData::InMemory(_) => {
+                    memory_size = memory_size.saturating_sub(evicted_entry.result.size());
+                }
+                CachedResultData::Spilled { .. } => {
+                    disk_size = disk_size.saturating_sub(evicted_entry.result.size());
+                }
+            }
+            remove_spilled_file(&evicted_entry.result.data);
+            self.unindex(evicted_key, &evicted_entry.result.input_tables)
+                .await;
+        }
+    }
+}
+
+/// Deletes a [`CachedResultData::Spilled`] entry's backing file; a no-op for in-memory entries.
+fn remove_spilled_file(data: &CachedResultData) {
+    if let CachedResultData::Spilled { path, .. } = data {
+        let _ = std::fs::remove_file(path.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    fn cached_result(input_tables: &[&str]) -> CachedQueryResult {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        CachedQueryResult {
+            data: CachedResultData::InMemory(Arc::new(Vec::new())),
+            schema,
+            input_tables: Arc::new(
+                input_tables
+                    .iter()
+                    .map(|t| TableReference::from(*t))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn test_config() -> QueryResultsCacheProviderConfig {
+        QueryResultsCacheProviderConfig {
+            max_size: u64::MAX,
+            spill_dir: std::env::temp_dir().join("spiceai-cache-test"),
+            max_disk_size: u64::MAX,
+            max_entries: NonZeroUsize::new(100).unwrap_or(NonZeroUsize::MIN),
+            max_age: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_for_table_evicts_dependent_entries() {
+        let provider = QueryResultsCacheProvider::new(test_config());
+
+        provider
+            .put_key(1, cached_result(&["customer"]))
+            .await
+            .expect("should cache result");
+        provider
+            .put_key(2, cached_result(&["orders"]))
+            .await
+            .expect("should cache result");
+
+        assert!(provider.get_key(1).await.is_some());
+        assert!(provider.get_key(2).await.is_some());
+
+        provider
+            .invalidate_for_table(&TableReference::from("customer"))
+            .await;
+
+        assert!(
+            provider.get_key(1).await.is_none(),
+            "entry depending on the invalidated table should be evicted"
+        );
+        assert!(
+            provider.get_key(2).await.is_some(),
+            "entry depending on a different table should be unaffected"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_for_table_with_no_dependents_is_a_no_op() {
+        let provider = QueryResultsCacheProvider::new(test_config());
+
+        provider
+            .put_key(1, cached_result(&["customer"]))
+            .await
+            .expect("should cache result");
+
+        provider
+            .invalidate_for_table(&TableReference::from("orders"))
+            .await;
+
+        assert!(provider.get_key(1).await.is_some());
+    }
+}
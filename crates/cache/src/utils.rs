@@ -22,12 +22,20 @@ use datafusion::{
     physical_plan::stream::RecordBatchStreamAdapter, sql::TableReference,
 };
 
-use crate::{CachedQueryResult, QueryResultsCacheProvider};
+use crate::{
+    provider::{CachedResultData, SpillWriter},
+    CachedQueryResult, QueryResultsCacheProvider,
+};
 
 use async_stream::stream;
 
 use futures::StreamExt;
 
+/// Collects `stream`'s batches for caching while passing every batch through unchanged. Once the
+/// collected batches cross `cache_provider`'s in-memory size budget, collection switches to
+/// spilling the remainder to a temporary Parquet file instead of dropping the result entirely --
+/// the query still gets cached, just on disk. If the on-disk budget is also exceeded, caching is
+/// abandoned for this query and any partial spill file is deleted.
 #[must_use]
 #[allow(clippy::implicit_hasher)]
 pub fn to_cached_record_batch_stream(
@@ -43,27 +51,71 @@ pub fn to_cached_record_batch_stream(
         let mut records: Vec<RecordBatch> = Vec::new();
         let mut records_size: usize = 0;
         let cache_max_size: usize = cache_provider.max_size().try_into().unwrap_or(usize::MAX);
+        let max_disk_size: u64 = cache_provider.max_disk_size();
+
+        let mut spill: Option<SpillWriter> = None;
+        let mut abandoned = false;
 
         while let Some(batch_result) = stream.next().await {
-            if records_size < cache_max_size {
-                if let Ok(batch) = &batch_result {
-                    records.push(batch.clone());
-                    records_size += batch.get_array_memory_size();
+            if let Ok(batch) = &batch_result {
+                if !abandoned {
+                    if spill.is_none() && records_size < cache_max_size {
+                        records.push(batch.clone());
+                        records_size += batch.get_array_memory_size();
+                    } else if spill.is_none() {
+                        match SpillWriter::create(cache_provider.spill_dir(), &schema_copy, &records) {
+                            Ok(writer) => spill = Some(writer),
+                            Err(e) => {
+                                tracing::warn!("Failed to start spilling query results to disk: {e}");
+                                abandoned = true;
+                            }
+                        }
+                    }
+
+                    if let Some(writer) = spill.as_mut() {
+                        if let Err(e) = writer.write(batch) {
+                            tracing::warn!("Failed to spill query results to disk: {e}");
+                            abandoned = true;
+                        } else if writer.size() > max_disk_size {
+                            tracing::debug!(
+                                "Query results exceeded the on-disk cache budget; not caching"
+                            );
+                            abandoned = true;
+                        }
+                    }
                 }
             }
 
             yield batch_result;
         }
 
-        if records_size < cache_max_size {
-            let cached_result = CachedQueryResult {
-                records: Arc::new(records),
-                schema: schema_copy,
-                input_tables,
+        if abandoned {
+            if let Some(writer) = spill {
+                writer.abandon();
+            }
+        } else {
+            let data = if let Some(writer) = spill {
+                match writer.finish() {
+                    Ok(data) => Some(data),
+                    Err(e) => {
+                        tracing::error!("Failed to finalize spilled query results: {e}");
+                        None
+                    }
+                }
+            } else {
+                Some(CachedResultData::InMemory(Arc::new(records)))
             };
 
-            if let Err(e) = cache_provider.put_key(plan_key, cached_result).await {
-                tracing::error!("Failed to cache query results: {e}");
+            if let Some(data) = data {
+                let cached_result = CachedQueryResult {
+                    data,
+                    schema: schema_copy,
+                    input_tables,
+                };
+
+                if let Err(e) = cache_provider.put_key(plan_key, cached_result).await {
+                    tracing::error!("Failed to cache query results: {e}");
+                }
             }
         }
     };
@@ -14,11 +14,17 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use async_openai::types::CreateChatCompletionRequest;
+use async_openai::types::{
+    ChatChoice, ChatCompletionMessageToolCall, ChatCompletionResponseMessage,
+    CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+    FinishReason, FunctionCall, Role,
+};
+use futures::StreamExt;
 use jsonpath_rust::JsonPath;
 use llms::chat::Chat;
 use serde_json::json;
 use std::{
+    collections::HashMap,
     str::FromStr,
     sync::{Arc, LazyLock},
 };
@@ -35,6 +41,12 @@ pub struct TestCase {
     /// Maps (id, `JSONPath` selector), where the selector is into the [`CreateChatCompletionResponse`].
     /// This is used in snapshot testing to assert certain properties of the response.
     pub json_path: Vec<(&'static str, &'static str)>,
+
+    /// Whether this case should also be exercised against the model's streaming API, with the
+    /// emitted deltas reassembled into a [`CreateChatCompletionResponse`] and checked against the
+    /// same `json_path` assertions (plus stream-specific invariants). Set to `false` for cases
+    /// that don't make sense to stream.
+    pub stream: bool,
 }
 
 /// Creates [`TestCase`] instances from request/response that JSON serialize to
@@ -42,11 +54,15 @@ pub struct TestCase {
 #[macro_export]
 macro_rules! test_case {
     ($name:expr, $req:expr, $jsonpaths:expr) => {
+        test_case!($name, $req, $jsonpaths, true)
+    };
+    ($name:expr, $req:expr, $jsonpaths:expr, $stream:expr) => {
         TestCase {
             name: $name,
             req: serde_json::from_value($req)
                 .expect(&format!("Failed to parse request in test case '{}'", $name)),
             json_path: $jsonpaths,
+            stream: $stream,
         }
     };
 }
@@ -197,7 +213,7 @@ static TEST_CASES: LazyLock<Vec<TestCase>> = LazyLock::new(|| {
 });
 
 #[allow(clippy::expect_used, clippy::expect_fun_call)]
-async fn run_single_test(test_name: &str, model_name: &str) -> Result<(), anyhow::Error> {
+async fn run_single_test(test_name: &str, model_name: &str, stream: bool) -> Result<(), anyhow::Error> {
     let _ = dotenvy::from_filename(".env").expect("failed to load .env file");
     init_tracing(None);
 
@@ -213,6 +229,11 @@ async fn run_single_test(test_name: &str, model_name: &str) -> Result<(), anyhow
         .find(|t| t.name == test_name)
         .expect("test case not found");
 
+    if stream && !test.stream {
+        tracing::debug!("Test case {test_name} is not exercised in streaming mode");
+        return Ok(());
+    }
+
     if TEST_ARGS.skip_model(model_name) {
         tracing::debug!("Skipping test {model_name}/{test_name}");
         return Ok(());
@@ -223,12 +244,25 @@ async fn run_single_test(test_name: &str, model_name: &str) -> Result<(), anyhow
         .find(|(name, _)| *name == model_name)
         .unwrap_or_else(|| panic!("model {model_name} not found"));
 
-    tracing::info!("Running test {test_name}/{model_name} with {:?}", test.req);
+    tracing::info!(
+        "Running test {test_name}/{model_name} (stream={stream}) with {:?}",
+        test.req
+    );
 
-    let actual_resp = model
-        .chat_request(test.req.clone())
-        .await
-        .unwrap_or_else(|_| panic!("For test {test_name}/{model_name}, chat_request failed"));
+    let actual_resp = if stream {
+        let chunks = model
+            .chat_stream(test.req.clone())
+            .await
+            .unwrap_or_else(|_| panic!("For test {test_name}/{model_name}, chat_stream failed"))
+            .collect::<Vec<_>>()
+            .await;
+        accumulate_stream(test_name, model_name, chunks)
+    } else {
+        model
+            .chat_request(test.req.clone())
+            .await
+            .unwrap_or_else(|_| panic!("For test {test_name}/{model_name}, chat_request failed"))
+    };
     tracing::trace!("Response for {test_name}/{model_name}: {actual_resp:?}");
 
     let resp_value =
@@ -238,14 +272,156 @@ async fn run_single_test(test_name: &str, model_name: &str) -> Result<(), anyhow
         let resp_ptr = JsonPath::from_str(json_ptr)
             .expect("invalid JSONPath selector")
             .find(&resp_value);
+        let suffix = if stream { "stream" } else { "sync" };
         insta::assert_snapshot!(
-            format!("{test_name}_{model_name}_{id}"),
+            format!("{test_name}_{model_name}_{id}_{suffix}"),
             serde_json::to_string_pretty(&resp_ptr).expect("Failed to serialize snapshot")
         );
     }
     Ok(())
 }
 
+/// Folds the deltas emitted by a model's streaming API back into the single
+/// [`CreateChatCompletionResponse`] shape the non-streaming assertions expect, so both modes can
+/// be checked with the same `json_path` selectors.
+///
+/// Also asserts the stream-specific invariants: a tool call's `arguments` arrive incrementally
+/// (across one or more chunks) and reassemble into valid JSON, and `finish_reason` is only set on
+/// the final chunk for each choice.
+#[allow(clippy::expect_used, clippy::expect_fun_call)]
+fn accumulate_stream(
+    test_name: &str,
+    model_name: &str,
+    chunks: Vec<Result<CreateChatCompletionStreamResponse, async_openai::error::OpenAIError>>,
+) -> CreateChatCompletionResponse {
+    let mut id = String::new();
+    let mut model = String::new();
+    let mut created = 0u32;
+    let mut object = String::new();
+
+    struct ChoiceAccumulator {
+        role: Role,
+        content: Option<String>,
+        refusal: Option<String>,
+        /// tool-call index -> (id, name, accumulated arguments, number of chunks it arrived in).
+        tool_calls: HashMap<u32, (String, String, String, usize)>,
+        finish_reason: Option<FinishReason>,
+    }
+
+    let mut choices: HashMap<u32, ChoiceAccumulator> = HashMap::new();
+
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let chunk = chunk.unwrap_or_else(|_| {
+            panic!("For test {test_name}/{model_name}, chunk {i} was an error")
+        });
+        id = chunk.id;
+        model = chunk.model;
+        created = chunk.created;
+        object = chunk.object;
+
+        for choice in chunk.choices {
+            let acc = choices.entry(choice.index).or_insert_with(|| ChoiceAccumulator {
+                role: Role::Assistant,
+                content: None,
+                refusal: None,
+                tool_calls: HashMap::new(),
+                finish_reason: None,
+            });
+
+            assert!(
+                acc.finish_reason.is_none(),
+                "finish_reason for {test_name}/{model_name} choice {} arrived before the final chunk",
+                choice.index
+            );
+
+            if let Some(role) = choice.delta.role {
+                acc.role = role;
+            }
+            if let Some(content) = choice.delta.content {
+                *acc.content.get_or_insert_with(String::new) += &content;
+            }
+            if let Some(refusal) = choice.delta.refusal {
+                *acc.refusal.get_or_insert_with(String::new) += &refusal;
+            }
+            for tool_call_chunk in choice.delta.tool_calls.unwrap_or_default() {
+                let entry = acc
+                    .tool_calls
+                    .entry(tool_call_chunk.index)
+                    .or_insert_with(|| (String::new(), String::new(), String::new(), 0));
+                if let Some(id) = tool_call_chunk.id {
+                    entry.0 = id;
+                }
+                if let Some(function) = tool_call_chunk.function {
+                    if let Some(name) = function.name {
+                        entry.1 += &name;
+                    }
+                    if let Some(arguments) = function.arguments {
+                        entry.2 += &arguments;
+                        entry.3 += 1;
+                    }
+                }
+            }
+            acc.finish_reason = choice.finish_reason;
+        }
+    }
+
+    let choices = choices
+        .into_iter()
+        .map(|(index, acc)| {
+            let mut tool_calls: Vec<_> = acc.tool_calls.into_iter().collect();
+            tool_calls.sort_by_key(|(index, _)| *index);
+            let tool_calls = tool_calls
+                .into_iter()
+                .map(|(_, (id, name, arguments, chunk_count))| {
+                    assert!(
+                        chunk_count > 0,
+                        "tool call arguments for {test_name}/{model_name} never arrived"
+                    );
+                    serde_json::from_str::<serde_json::Value>(&arguments).unwrap_or_else(|e| {
+                        panic!(
+                            "tool call arguments for {test_name}/{model_name} did not reassemble into valid JSON: {e}"
+                        )
+                    });
+                    ChatCompletionMessageToolCall {
+                        id,
+                        r#type: async_openai::types::ChatCompletionToolType::Function,
+                        function: FunctionCall { name, arguments },
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            ChatChoice {
+                index,
+                message: ChatCompletionResponseMessage {
+                    content: acc.content,
+                    refusal: acc.refusal,
+                    tool_calls: if tool_calls.is_empty() {
+                        None
+                    } else {
+                        Some(tool_calls)
+                    },
+                    role: acc.role,
+                    function_call: None,
+                    audio: None,
+                },
+                finish_reason: acc.finish_reason,
+                logprobs: None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    CreateChatCompletionResponse {
+        id,
+        choices,
+        created,
+        model,
+        service_tier: None,
+        system_fingerprint: None,
+        object,
+        usage: None,
+    }
+}
+
 // Macro to create test module and functions
 #[macro_export]
 macro_rules! generate_model_tests {
@@ -255,9 +431,15 @@ macro_rules! generate_model_tests {
                 paste::paste! {
                     #[tokio::test]
                     async fn [<test_ $model_name_expr _ $test_case_expr>]() {
-                        run_single_test(stringify!($test_case_expr), stringify!($model_name_expr)).await
+                        run_single_test(stringify!($test_case_expr), stringify!($model_name_expr), false).await
                             .expect("test failed");
                     }
+
+                    #[tokio::test]
+                    async fn [<test_ $model_name_expr _ $test_case_expr _stream>]() {
+                        run_single_test(stringify!($test_case_expr), stringify!($model_name_expr), true).await
+                            .expect("streaming test failed");
+                    }
                 }
             };
         }
@@ -0,0 +1,117 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::http::{HeaderMap, HeaderValue};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How long a caller waits for an in-flight slot before being told to back off.
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Configuration for a [`ConcurrencyLimiter`].
+#[derive(Debug, Clone, Copy)]
+pub struct ConcurrencyLimiterConfig {
+    /// The maximum number of query-executing requests allowed in flight at once.
+    pub max_concurrent_queries: usize,
+    /// How long to wait for a permit before rejecting the request.
+    pub acquire_timeout: Duration,
+}
+
+impl Default for ConcurrencyLimiterConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_queries: 64,
+            acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+        }
+    }
+}
+
+/// Admission control in front of endpoints that execute arbitrary DataFusion queries (the SQL
+/// `/v1/sql` endpoint, dataset `sample`, and any other API surface -- Flight included -- that
+/// wants to bound how many expensive queries run concurrently).
+///
+/// Shared via an [`Extension`](axum::extract::Extension) so all such endpoints draw from the same
+/// budget.
+#[derive(Clone)]
+pub struct ConcurrencyLimiter {
+    semaphore: Arc<Semaphore>,
+    config: ConcurrencyLimiterConfig,
+}
+
+/// An acquired slot. Releases its permit back to the limiter when dropped. Owns its permit (rather
+/// than borrowing the limiter) so it can be held for the lifetime of a streamed response body --
+/// moved into the stream task and dropped only once the stream itself is fully drained or
+/// dropped -- instead of just until the initial `Response` is constructed.
+pub struct LimiterPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ConcurrencyLimiter {
+    #[must_use]
+    pub fn new(config: ConcurrencyLimiterConfig) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(config.max_concurrent_queries)),
+            config,
+        }
+    }
+
+    #[must_use]
+    pub fn limit(&self) -> usize {
+        self.config.max_concurrent_queries
+    }
+
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    /// Attempts to acquire an in-flight slot, waiting up to the configured timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns the configured `acquire_timeout` (for use as a `Retry-After` duration) if no slot
+    /// became available in time.
+    pub async fn acquire(&self) -> Result<LimiterPermit, Duration> {
+        let deadline = Instant::now() + self.config.acquire_timeout;
+        let semaphore = Arc::clone(&self.semaphore);
+        match tokio::time::timeout_at(deadline.into(), semaphore.acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(LimiterPermit { _permit: permit }),
+            _ => Err(self.config.acquire_timeout),
+        }
+    }
+
+    /// Builds the standard `X-RateLimit-*` headers describing the current in-flight budget.
+    #[must_use]
+    pub fn rate_limit_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-ratelimit-limit",
+            HeaderValue::from(self.limit() as u64),
+        );
+        headers.insert(
+            "x-ratelimit-remaining",
+            HeaderValue::from(self.remaining() as u64),
+        );
+        headers.insert(
+            "x-ratelimit-reset",
+            HeaderValue::from(self.config.acquire_timeout.as_secs().max(1)),
+        );
+        headers
+    }
+}
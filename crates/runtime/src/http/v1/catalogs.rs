@@ -25,15 +25,11 @@ use axum::{
 };
 use axum_extra::TypedHeader;
 use headers_accept::Accept;
-use mediatype::{
-    names::{APPLICATION, CSV, JSON, TEXT},
-    MediaType,
-};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use tract_core::tract_data::itertools::Itertools;
 
-use super::{convert_entry_to_csv, Format};
+use super::{convert_entry_to_csv, convert_entry_to_ipc, convert_entry_to_parquet, Format};
 
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
@@ -50,10 +46,6 @@ pub(crate) struct CatalogResponseItem {
     pub name: String,
 }
 
-const APPLICATION_JSON: MediaType = MediaType::from_parts(APPLICATION, JSON, None, &[]);
-const TEXT_CSV: MediaType = MediaType::from_parts(TEXT, CSV, None, &[]);
-const ACCEPT_LIST: &[MediaType; 2] = &[APPLICATION_JSON, TEXT_CSV];
-
 /// Get a list of catalogs.
 #[cfg_attr(feature = "openapi", utoipa::path(
     get,
@@ -78,6 +70,9 @@ from,name
 spiceai,spiceai
 "
         ))),
+        (status = 200, description = "List of catalogs as an Arrow IPC stream", content((
+            Vec<u8> = "application/vnd.apache.arrow.stream"
+        ))),
         (status = 500, description = "Internal server error occurred while processing catalogs", content((
             serde_json::Value = "application/json",
             example = json!({
@@ -117,14 +112,7 @@ pub(crate) async fn get(
         })
         .collect_vec();
 
-    let mut format = Format::Json;
-    if let Some(accept) = accept {
-        if let Some(media_type) = accept.negotiate(ACCEPT_LIST.iter()) {
-            if let ("text", "csv") = (media_type.ty.as_str(), media_type.subty.as_str()) {
-                format = Format::Csv;
-            }
-        }
-    }
+    let format = Format::from_accept_header(accept.as_ref());
 
     match format {
         Format::Json => (status::StatusCode::OK, Json(resp)).into_response(),
@@ -135,5 +123,29 @@ pub(crate) async fn get(
                 (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
             }
         },
+        Format::Parquet => match convert_entry_to_parquet(&resp) {
+            Ok(parquet) => (
+                status::StatusCode::OK,
+                [(axum::http::header::CONTENT_TYPE, Format::Parquet.content_type())],
+                parquet,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Error converting to Parquet: {e}");
+                (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        },
+        Format::Ipc => match convert_entry_to_ipc(&resp) {
+            Ok(ipc) => (
+                status::StatusCode::OK,
+                [(axum::http::header::CONTENT_TYPE, Format::Ipc.content_type())],
+                ipc,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Error converting to Arrow IPC: {e}");
+                (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        },
     }
 }
@@ -13,11 +13,16 @@ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 See the License for the specific language governing permissions and
 limitations under the License.
 */
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::{Arc, LazyLock, Mutex},
+};
 
 use crate::{
     accelerated_table::refresh::RefreshOverrides,
     component::dataset::Dataset,
+    http::limits::ConcurrencyLimiter,
     tools::builtin::sample::{
         distinct::DistinctColumnsParams, random::RandomSampleParams, top_samples::TopSamplesParams,
         SampleFrom, SampleTableMethod, SampleTableParams,
@@ -32,22 +37,78 @@ use axum::{
     response::{IntoResponse, Response},
     Extension, Json,
 };
-use axum_extra::TypedHeader;
+use axum_extra::{
+    headers::{ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, LastModified},
+    TypedHeader,
+};
+use cache::QueryResultsCacheProvider;
 use datafusion::sql::TableReference;
 use headers_accept::Accept;
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use time::OffsetDateTime;
 use tokio::sync::RwLock;
 use tract_core::tract_data::itertools::Itertools;
 
 use crate::{datafusion::DataFusion, status::ComponentStatus};
 
 use super::{
-    arrow_to_csv, arrow_to_json, arrow_to_plain, convert_entry_to_csv, dataset_status, ArrowFormat,
-    Format,
+    arrow_to_csv, arrow_to_ipc, arrow_to_json, arrow_to_ndjson, arrow_to_parquet, arrow_to_plain,
+    convert_entry_to_csv, convert_entry_to_ipc, convert_entry_to_parquet, dataset_status,
+    jobs::{JobId, JobRegistry, JobState, JobStatus},
+    ApiError, ArrowFormat, Format,
 };
 
+/// Tracks the identity and reload generation of the currently-loaded `App` (which is replaced
+/// wholesale on every spicepod reload/acceleration patch) and the time the current generation was
+/// first observed, so `get` can derive a `Last-Modified` timestamp without the dataset-listing
+/// handler needing its own reload hook.
+///
+/// Holds a strong reference to the last-seen `App`, not just its address: comparing bare pointer
+/// values would be vulnerable to the allocator reusing a freed `Arc<App>`'s address for an
+/// unrelated later instance, which would make a real config change look like no change at all.
+/// Keeping the `Arc` alive here rules that out, since its memory can't be reused while we're still
+/// holding a reference to it.
+static LAST_APP_RELOAD: LazyLock<Mutex<(Option<Arc<App>>, u64, OffsetDateTime)>> =
+    LazyLock::new(|| Mutex::new((None, 0, OffsetDateTime::now_utc())));
+
+fn last_modified_for(app: &Arc<App>) -> OffsetDateTime {
+    let mut last_reload = LAST_APP_RELOAD
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    let reloaded = match &last_reload.0 {
+        Some(last_app) => !Arc::ptr_eq(last_app, app),
+        None => true,
+    };
+
+    if reloaded {
+        let generation = last_reload.1 + 1;
+        tracing::debug!("Detected app reload (generation {generation})");
+        *last_reload = (Some(Arc::clone(app)), generation, OffsetDateTime::now_utc());
+    }
+
+    last_reload.2
+}
+
+/// A stable ETag for a dataset-listing response: a hash of the serialized items, plus the config
+/// generation implied by `last_modified` so the value also changes across reloads that happen to
+/// serialize identically (e.g. a no-op spicepod reload).
+fn etag_for(resp: &[DatasetResponseItem], last_modified: OffsetDateTime) -> ETag {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    if let Ok(serialized) = serde_json::to_vec(resp) {
+        serialized.hash(&mut hasher);
+    }
+    last_modified.unix_timestamp_nanos().hash(&mut hasher);
+    let value = format!("\"{:016x}\"", hasher.finish());
+    value.parse().unwrap_or_else(|_| {
+        "\"0\""
+            .parse()
+            .unwrap_or_else(|_| unreachable!("\"0\" is always a valid ETag"))
+    })
+}
+
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
 pub struct DatasetFilter {
@@ -62,7 +123,8 @@ pub struct DatasetQueryParams {
     #[serde(default)]
     status: bool,
 
-    /// The format of the response. Possible values are 'json' (default) or 'csv'.
+    /// The format of the response. Possible values are 'json' (default), 'csv', 'parquet', or
+    /// 'ipc'.
     #[serde(default)]
     format: Format,
 }
@@ -153,6 +215,8 @@ pub(crate) async fn get(
     Extension(df): Extension<Arc<DataFusion>>,
     Query(filter): Query<DatasetFilter>,
     Query(params): Query<DatasetQueryParams>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
 ) -> Response {
     let app_lock = tokio::select! {
         lock = app.read() => lock,
@@ -196,7 +260,28 @@ pub(crate) async fn get(
         })
         .collect_vec();
 
-    match params.format {
+    let last_modified = last_modified_for(readable_app);
+    let etag = etag_for(&resp, last_modified);
+    let system_last_modified = std::time::SystemTime::from(last_modified);
+
+    // RFC 7232: when If-None-Match is present, it alone determines the outcome -- If-Modified-Since
+    // is only consulted as a fallback when the client didn't send an If-None-Match at all.
+    let not_modified = if let Some(h) = if_none_match {
+        !h.0.precondition_passes(&etag)
+    } else {
+        if_modified_since.is_some_and(|h| !h.0.is_modified(system_last_modified))
+    };
+
+    if not_modified {
+        let mut response = status::StatusCode::NOT_MODIFIED.into_response();
+        response.headers_mut().typed_insert(etag);
+        response
+            .headers_mut()
+            .typed_insert(LastModified::from(system_last_modified));
+        return response;
+    }
+
+    let mut response = match params.format {
         Format::Json => (status::StatusCode::OK, Json(resp)).into_response(),
         Format::Csv => match convert_entry_to_csv(&resp) {
             Ok(csv) => (status::StatusCode::OK, csv).into_response(),
@@ -205,7 +290,39 @@ pub(crate) async fn get(
                 (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
             }
         },
+        Format::Parquet => match convert_entry_to_parquet(&resp) {
+            Ok(parquet) => (
+                status::StatusCode::OK,
+                [(http::header::CONTENT_TYPE, Format::Parquet.content_type())],
+                parquet,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Error converting to Parquet: {e}");
+                (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        },
+        Format::Ipc => match convert_entry_to_ipc(&resp) {
+            Ok(ipc) => (
+                status::StatusCode::OK,
+                [(http::header::CONTENT_TYPE, Format::Ipc.content_type())],
+                ipc,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Error converting to Arrow IPC: {e}");
+                (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        },
+    };
+
+    if response.status().is_success() {
+        response.headers_mut().typed_insert(etag);
+        response
+            .headers_mut()
+            .typed_insert(LastModified::from(system_last_modified));
     }
+    response
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -223,9 +340,30 @@ pub struct AccelerationRequest {
     pub refresh_sql: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct RefreshQueryParams {
+    /// If true, blocks until the refresh job completes (or `REFRESH_WAIT_TIMEOUT` elapses) and
+    /// responds synchronously, preserving the pre-job-registry behavior. Default is false.
+    #[serde(default)]
+    wait: bool,
+}
+
+/// How long `?wait=true` blocks for the refresh job to finish before falling back to a `202`.
+const REFRESH_WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub(crate) struct RefreshJobResponse {
+    pub job_id: JobId,
+    pub message: String,
+}
+
 /// Trigger an on-demand refresh for an accelerated dataset.
 ///
-/// This endpoint triggers an on-demand refresh for an accelerated dataset.
+/// This endpoint enqueues an on-demand refresh for an accelerated dataset into the runtime's job
+/// registry and returns immediately with a job id; poll `GET /v1/datasets/acceleration/refresh/{job_id}`
+/// for status. Pass `?wait=true` to block for the refresh to finish and get a synchronous result instead.
 /// The refresh only applies to `full` and `append` refresh modes (not `changes` mode).
 #[cfg_attr(feature = "openapi", utoipa::path(
     post,
@@ -233,7 +371,8 @@ pub struct AccelerationRequest {
     operation_id = "post_dataset_refresh",
     tag = "Datasets",
     params(
-        ("name" = String, Path, description = "The name of the dataset to refresh.")
+        ("name" = String, Path, description = "The name of the dataset to refresh."),
+        RefreshQueryParams
     ),
     request_body(
         description = "On-demand refresh request for a specific dataset.",
@@ -245,10 +384,11 @@ pub struct AccelerationRequest {
         ))
     ),
     responses(
-        (status = 201, description = "Dataset refresh triggered successfully", content((
-            MessageResponse = "application/json",
+        (status = 202, description = "Dataset refresh job enqueued", content((
+            RefreshJobResponse = "application/json",
             example = json!({
-                "message": "Dataset refresh triggered for taxi_trips."
+                "job_id": "5b1b8b61-7c1d-4b7e-9e4b-2e6f3a6b9b2a",
+                "message": "Dataset refresh job enqueued for taxi_trips."
             })
         ))),
         (status = 404, description = "Dataset not found", content((
@@ -274,7 +414,10 @@ pub struct AccelerationRequest {
 pub(crate) async fn refresh(
     Extension(app): Extension<Arc<RwLock<Option<Arc<App>>>>>,
     Extension(df): Extension<Arc<DataFusion>>,
+    Extension(jobs): Extension<Arc<JobRegistry>>,
+    Extension(query_results_cache): Extension<Arc<QueryResultsCacheProvider>>,
     Path(dataset_name): Path<String>,
+    Query(params): Query<RefreshQueryParams>,
     overrides_opt: Option<Json<RefreshOverrides>>,
     // When this is an Option<Json>, Json rejections are silenced
     // This means malformed Json, etc, will simply return None
@@ -319,24 +462,86 @@ pub(crate) async fn refresh(
             .into_response();
     };
 
-    match df
-        .refresh_table(
-            &TableReference::parse_str(dataset.name.as_str()),
-            overrides_opt.map(|Json(overrides)| overrides),
-        )
-        .await
-    {
-        Ok(()) => (
+    let table_ref = TableReference::parse_str(dataset.name.as_str());
+    let overrides = overrides_opt.map(|Json(overrides)| overrides);
+
+    let job_id = jobs.enqueue();
+    let job_handle = {
+        let jobs = Arc::clone(&jobs);
+        let job_id = job_id.clone();
+        let df = Arc::clone(&df);
+        let query_results_cache = Arc::clone(&query_results_cache);
+        tokio::spawn(async move {
+            jobs.mark_running(&job_id);
+            match df.refresh_table(&table_ref, overrides).await {
+                Ok(()) => {
+                    query_results_cache.invalidate_for_table(&table_ref).await;
+                    jobs.mark_completed(&job_id, None);
+                }
+                Err(err) => jobs.mark_failed(&job_id, err.to_string()),
+            }
+        })
+    };
+
+    if params.wait {
+        let _ = tokio::time::timeout(REFRESH_WAIT_TIMEOUT, job_handle).await;
+    }
+
+    match (params.wait, jobs.get(&job_id)) {
+        (true, Some(JobState { status: JobStatus::Completed { .. }, .. })) => (
             status::StatusCode::CREATED,
             Json(MessageResponse {
                 message: format!("Dataset refresh triggered for {dataset_name}."),
             }),
         )
             .into_response(),
-        Err(err) => (
+        (true, Some(JobState { status: JobStatus::Failed { error }, .. })) => (
             status::StatusCode::INTERNAL_SERVER_ERROR,
+            Json(MessageResponse { message: error }),
+        )
+            .into_response(),
+        _ => (
+            status::StatusCode::ACCEPTED,
+            Json(RefreshJobResponse {
+                job_id: job_id.clone(),
+                message: format!("Dataset refresh job enqueued for {dataset_name}."),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Poll the status of a backgrounded acceleration refresh job.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/v1/datasets/acceleration/refresh/{job_id}",
+    operation_id = "get_dataset_refresh_job",
+    tag = "Datasets",
+    params(
+        ("job_id" = String, Path, description = "The id of the refresh job returned by the refresh endpoint.")
+    ),
+    responses(
+        (status = 200, description = "The current state of the refresh job.", content((
+            JobState = "application/json"
+        ))),
+        (status = 404, description = "No job with the given id was found", content((
+            MessageResponse = "application/json",
+            example = json!({
+                "message": "Job 5b1b8b61-7c1d-4b7e-9e4b-2e6f3a6b9b2a not found"
+            })
+        )))
+    )
+))]
+pub(crate) async fn refresh_job_status(
+    Extension(jobs): Extension<Arc<JobRegistry>>,
+    Path(job_id): Path<JobId>,
+) -> Response {
+    match jobs.get(&job_id) {
+        Some(job) => (status::StatusCode::OK, Json(job)).into_response(),
+        None => (
+            status::StatusCode::NOT_FOUND,
             Json(MessageResponse {
-                message: format!("{err}"),
+                message: format!("Job {job_id} not found"),
             }),
         )
             .into_response(),
@@ -525,10 +730,37 @@ value2,456
 ))]
 pub(crate) async fn sample(
     Extension(df): Extension<Arc<DataFusion>>,
+    Extension(limiter): Extension<Arc<ConcurrencyLimiter>>,
     accept: Option<TypedHeader<Accept>>,
-    Query(query): Query<SampleQueryParams>,
+    query: Query<SampleQueryParams>,
     body: String,
 ) -> Response {
+    let result = sample_impl(df, &limiter, accept, query, body).await;
+
+    // Computed after `sample_impl` has returned (and so after its permit, if any was acquired,
+    // has already been dropped) so the headers reflect the budget every caller sees next, and
+    // attached regardless of outcome so 429/503/400/500 responses carry them too, not just 200s.
+    let rate_limit_headers = limiter.rate_limit_headers();
+    let mut response = match result {
+        Ok(response) => response,
+        Err(err) => err.into_response(),
+    };
+    response.headers_mut().extend(rate_limit_headers);
+    response
+}
+
+async fn sample_impl(
+    df: Arc<DataFusion>,
+    limiter: &ConcurrencyLimiter,
+    accept: Option<TypedHeader<Accept>>,
+    Query(query): Query<SampleQueryParams>,
+    body: String,
+) -> Result<Response, ApiError> {
+    let permit = limiter
+        .acquire()
+        .await
+        .map_err(|retry_after| ApiError::ServiceUnavailable { retry_after })?;
+
     // Convulted way to handle parsing [`SampleTableParams`] since params might overlap. Allow
     // users to specify the type of sampling they want.
     let params_result = match query.r#type {
@@ -545,27 +777,30 @@ pub(crate) async fn sample(
         None => serde_json::from_str::<SampleTableParams>(&body),
     };
 
-    let Ok(params) = params_result else {
-        return (status::StatusCode::BAD_REQUEST, "Invalid request body").into_response();
-    };
+    let params = params_result
+        .map_err(|e| ApiError::BadRequest(format!("Invalid request body: {e}")))?;
 
-    let sample = match params.sample(df).await {
-        Ok(sample) => sample,
-        Err(e) => {
-            return (status::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
-        }
-    };
+    let sample = params.sample(df).await.map_err(|e| ApiError::from(anyhow::anyhow!(e)))?;
+    drop(permit);
 
-    let res = match ArrowFormat::from_accept_header(accept.as_ref()) {
-        ArrowFormat::Json => arrow_to_json(&[sample]),
-        ArrowFormat::Csv => arrow_to_csv(&[sample]),
-        ArrowFormat::Plain => arrow_to_plain(&[sample]),
+    let format = ArrowFormat::from_accept_header(accept.as_ref());
+    let body: Vec<u8> = match format {
+        ArrowFormat::Json => arrow_to_json(&[sample])?.into_bytes(),
+        ArrowFormat::Csv => arrow_to_csv(&[sample])?.into_bytes(),
+        ArrowFormat::Plain => arrow_to_plain(&[sample])?.into_bytes(),
+        ArrowFormat::IpcStream => arrow_to_ipc(&[sample])?,
+        ArrowFormat::NdJson => arrow_to_ndjson(&[sample])?.into_bytes(),
+        ArrowFormat::Parquet => arrow_to_parquet(&[sample])?,
     };
 
-    match res {
-        Ok(body) => (StatusCode::OK, body).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
-    }
+    let response = (
+        StatusCode::OK,
+        [(http::header::CONTENT_TYPE, format.content_type())],
+        body,
+    )
+        .into_response();
+
+    Ok(response)
 }
 
 fn dataset_properties(ds: &Dataset) -> HashMap<String, Value> {
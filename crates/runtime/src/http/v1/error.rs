@@ -0,0 +1,109 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::time::Duration;
+
+use axum::{
+    http::{HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use datafusion::error::DataFusionError;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct ApiErrorBody {
+    error: String,
+}
+
+/// A structured error type for the `/v1` HTTP API, mapped to an appropriate HTTP status code by
+/// [`IntoResponse`] instead of every handler collapsing failures into a bare 500.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ApiError {
+    #[error("{0} not found")]
+    NotFound(String),
+
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("too many requests")]
+    TooManyRequests { retry_after: Duration },
+
+    #[error("service unavailable")]
+    ServiceUnavailable { retry_after: Duration },
+
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ApiError::TooManyRequests { retry_after } | ApiError::ServiceUnavailable { retry_after } => {
+                Some(*retry_after)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if let ApiError::Internal(ref e) = self {
+            tracing::error!("Internal error handling API request: {e}");
+        }
+
+        let status = self.status_code();
+        let retry_after = self.retry_after();
+        let body = ApiErrorBody {
+            error: self.to_string(),
+        };
+
+        let mut response = (status, Json(body)).into_response();
+        if let Some(retry_after) = retry_after {
+            if let Ok(value) = HeaderValue::from_str(&retry_after.as_secs().max(1).to_string()) {
+                response.headers_mut().insert("retry-after", value);
+            }
+        }
+        response
+    }
+}
+
+/// Maps a DataFusion error raised while planning/executing a user-supplied SQL statement to the
+/// appropriate client/server HTTP status code: malformed or unplannable SQL is a client fault,
+/// resource exhaustion means the caller should back off, and everything else is an internal error.
+impl From<DataFusionError> for ApiError {
+    fn from(err: DataFusionError) -> Self {
+        match err {
+            DataFusionError::SQL(..) | DataFusionError::Plan(_) | DataFusionError::SchemaError(..) => {
+                ApiError::BadRequest(err.to_string())
+            }
+            DataFusionError::ResourcesExhausted(_) => ApiError::TooManyRequests {
+                retry_after: Duration::from_secs(1),
+            },
+            other => ApiError::Internal(other.into()),
+        }
+    }
+}
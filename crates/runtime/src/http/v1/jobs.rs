@@ -0,0 +1,133 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::fmt;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Identifies a single backgrounded job (currently only acceleration refreshes).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub(crate) struct JobId(String);
+
+impl JobId {
+    fn new() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for JobId {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+/// The current lifecycle state of a [`JobId`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub(crate) enum JobStatus {
+    Queued,
+    Running,
+    Completed { rows_loaded: Option<u64> },
+    Failed { error: String },
+}
+
+/// A job's full record, as returned by the status-polling endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub(crate) struct JobState {
+    pub job_id: JobId,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+}
+
+/// An in-process registry of backgrounded jobs, keyed by [`JobId`].
+///
+/// This is intentionally in-memory only: job state does not survive a runtime restart, which
+/// matches the "best effort" visibility this subsystem provides today.
+#[derive(Default)]
+pub(crate) struct JobRegistry {
+    jobs: DashMap<JobId, JobState>,
+}
+
+impl JobRegistry {
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a new job in the `Queued` state and returns its id.
+    pub(crate) fn enqueue(&self) -> JobId {
+        let job_id = JobId::new();
+        self.jobs.insert(
+            job_id.clone(),
+            JobState {
+                job_id: job_id.clone(),
+                status: JobStatus::Queued,
+                started_at: OffsetDateTime::now_utc(),
+                finished_at: None,
+            },
+        );
+        job_id
+    }
+
+    pub(crate) fn mark_running(&self, job_id: &JobId) {
+        if let Some(mut job) = self.jobs.get_mut(job_id) {
+            job.status = JobStatus::Running;
+        }
+    }
+
+    pub(crate) fn mark_completed(&self, job_id: &JobId, rows_loaded: Option<u64>) {
+        if let Some(mut job) = self.jobs.get_mut(job_id) {
+            job.status = JobStatus::Completed { rows_loaded };
+            job.finished_at = Some(OffsetDateTime::now_utc());
+        }
+    }
+
+    pub(crate) fn mark_failed(&self, job_id: &JobId, error: String) {
+        if let Some(mut job) = self.jobs.get_mut(job_id) {
+            job.status = JobStatus::Failed { error };
+            job.finished_at = Some(OffsetDateTime::now_utc());
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn get(&self, job_id: &JobId) -> Option<JobState> {
+        self.jobs.get(job_id).map(|entry| entry.clone())
+    }
+
+    #[must_use]
+    pub(crate) fn is_finished(&self, job_id: &JobId) -> bool {
+        self.jobs
+            .get(job_id)
+            .is_some_and(|job| matches!(job.status, JobStatus::Completed { .. } | JobStatus::Failed { .. }))
+    }
+}
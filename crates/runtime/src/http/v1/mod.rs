@@ -0,0 +1,461 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::sync::Arc;
+
+use arrow::record_batch::RecordBatch;
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    component::dataset::Dataset, datafusion::DataFusion, http::limits::LimiterPermit,
+    status::ComponentStatus,
+};
+
+pub(crate) mod catalogs;
+pub(crate) mod datasets;
+mod error;
+pub(crate) mod jobs;
+pub(crate) mod query;
+
+pub(crate) use error::ApiError;
+
+/// The response format for list-style `/v1` endpoints (datasets, catalogs, ...).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    #[default]
+    Json,
+    Csv,
+    Parquet,
+    Ipc,
+}
+
+impl Format {
+    /// Negotiates a response format from the request's `Accept` header. For listing endpoints
+    /// that take format as an explicit `?format=` query param, prefer that when the caller set it;
+    /// use this as a fallback (or the sole mechanism, for endpoints with no query param) so every
+    /// listing endpoint recognizes the same set of media types. Mirrors
+    /// [`ArrowFormat::from_accept_header`]'s matching.
+    pub(crate) fn from_accept_header(accept: Option<&axum_extra::TypedHeader<Accept>>) -> Self {
+        let Some(accept) = accept else {
+            return Self::default();
+        };
+
+        if accept
+            .media_types()
+            .any(|m| m.ty() == "application" && m.subty() == "vnd.apache.arrow.stream")
+        {
+            Self::Ipc
+        } else if accept
+            .media_types()
+            .any(|m| m.ty() == "application" && m.subty() == "vnd.apache.parquet")
+        {
+            Self::Parquet
+        } else if accept.media_types().any(|m| m.subty() == "csv") {
+            Self::Csv
+        } else {
+            Self::Json
+        }
+    }
+
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv",
+            Format::Parquet => "application/vnd.apache.parquet",
+            Format::Ipc => "application/vnd.apache.arrow.stream",
+        }
+    }
+}
+
+/// The response format for endpoints returning `RecordBatch`es (sample, SQL query).
+///
+/// For SQL query results, `IpcStream` and `NdJson` are streamed to the client batch-by-batch as
+/// the query produces them, rather than buffered in memory like the other variants, so they're
+/// the preferred formats for large result sets. `sample` buffers its single result batch in every
+/// format.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArrowFormat {
+    #[default]
+    Json,
+    Csv,
+    Plain,
+    IpcStream,
+    NdJson,
+    Parquet,
+}
+
+impl ArrowFormat {
+    pub(crate) fn from_accept_header(accept: Option<&axum_extra::TypedHeader<Accept>>) -> Self {
+        let Some(accept) = accept else {
+            return Self::default();
+        };
+
+        if accept
+            .media_types()
+            .any(|m| m.ty() == "application" && m.subty() == "vnd.apache.arrow.stream")
+        {
+            Self::IpcStream
+        } else if accept
+            .media_types()
+            .any(|m| m.ty() == "application" && m.subty() == "vnd.apache.parquet")
+        {
+            Self::Parquet
+        } else if accept
+            .media_types()
+            .any(|m| m.subty() == "x-ndjson" || m.suffix() == Some("ndjson"))
+        {
+            Self::NdJson
+        } else if accept.media_types().any(|m| m.subty() == "csv") {
+            Self::Csv
+        } else if accept.media_types().any(|m| m.subty() == "plain") {
+            Self::Plain
+        } else {
+            Self::Json
+        }
+    }
+
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            ArrowFormat::Json => "application/json",
+            ArrowFormat::Csv => "text/csv",
+            ArrowFormat::Plain => "text/plain",
+            ArrowFormat::IpcStream => "application/vnd.apache.arrow.stream",
+            ArrowFormat::NdJson => "application/x-ndjson",
+            ArrowFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+use headers_accept::Accept;
+
+pub(crate) fn arrow_to_json(batches: &[RecordBatch]) -> Result<String, anyhow::Error> {
+    let buf = Vec::new();
+    let mut writer = arrow_json::ArrayWriter::new(buf);
+    writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+pub(crate) fn arrow_to_csv(batches: &[RecordBatch]) -> Result<String, anyhow::Error> {
+    let mut buf = Vec::new();
+    {
+        let mut writer = arrow_csv::Writer::new(&mut buf);
+        for batch in batches {
+            writer.write(batch)?;
+        }
+    }
+    Ok(String::from_utf8(buf)?)
+}
+
+pub(crate) fn arrow_to_plain(batches: &[RecordBatch]) -> Result<String, anyhow::Error> {
+    Ok(arrow_cast::pretty::pretty_format_batches(batches)?.to_string())
+}
+
+/// Buffered (non-streaming) Arrow IPC stream encoding, for small, already-materialized batches
+/// (e.g. a single `sample` result). For query results large enough to warrant streaming, see
+/// [`ipc_stream_response`].
+pub(crate) fn arrow_to_ipc(batches: &[RecordBatch]) -> Result<Vec<u8>, anyhow::Error> {
+    let Some(first) = batches.first() else {
+        return Ok(Vec::new());
+    };
+    let mut buf = Vec::new();
+    {
+        let mut writer = arrow_ipc::writer::StreamWriter::try_new(&mut buf, &first.schema())?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+    }
+    Ok(buf)
+}
+
+/// Buffered (non-streaming) NDJSON encoding, for small, already-materialized batches. For query
+/// results large enough to warrant streaming, see [`ndjson_stream_response`].
+pub(crate) fn arrow_to_ndjson(batches: &[RecordBatch]) -> Result<String, anyhow::Error> {
+    let mut buf = Vec::new();
+    {
+        let mut writer = arrow_json::writer::LineDelimitedWriter::new(&mut buf);
+        writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+        writer.finish()?;
+    }
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Encodes `batches` as a Parquet file (`application/vnd.apache.parquet`), for clients that want
+/// to pull sampled/queried data directly into a columnar file without a lossy CSV round-trip.
+pub(crate) fn arrow_to_parquet(batches: &[RecordBatch]) -> Result<Vec<u8>, anyhow::Error> {
+    let Some(first) = batches.first() else {
+        return Ok(Vec::new());
+    };
+    let mut buf = Vec::new();
+    {
+        let mut writer = parquet::arrow::ArrowWriter::try_new(&mut buf, first.schema(), None)?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.close()?;
+    }
+    Ok(buf)
+}
+
+pub(crate) fn convert_entry_to_csv<T: Serialize>(entries: &[T]) -> Result<String, anyhow::Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for entry in entries {
+        writer.serialize(entry)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Encodes a list-style `/v1` response (e.g. `DatasetResponseItem`) as a Parquet file by
+/// round-tripping through NDJSON to infer an Arrow schema, then writing it via
+/// [`arrow_to_parquet`].
+pub(crate) fn convert_entry_to_parquet<T: Serialize>(entries: &[T]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut ndjson = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut ndjson, entry)?;
+        ndjson.push(b'\n');
+    }
+
+    let cursor = std::io::Cursor::new(ndjson);
+    let (schema, _) = arrow_json::reader::infer_json_schema_from_seekable(cursor.clone(), None)?;
+    let mut reader = arrow_json::ReaderBuilder::new(Arc::new(schema)).build(cursor)?;
+
+    let mut batches = Vec::new();
+    while let Some(batch) = reader.next() {
+        batches.push(batch?);
+    }
+
+    arrow_to_parquet(&batches)
+}
+
+/// Encodes a list-style `/v1` response as an Arrow IPC stream, via the same NDJSON round-trip
+/// [`convert_entry_to_parquet`] uses to infer a schema.
+pub(crate) fn convert_entry_to_ipc<T: Serialize>(entries: &[T]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut ndjson = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut ndjson, entry)?;
+        ndjson.push(b'\n');
+    }
+
+    let cursor = std::io::Cursor::new(ndjson);
+    let (schema, _) = arrow_json::reader::infer_json_schema_from_seekable(cursor.clone(), None)?;
+    let mut reader = arrow_json::ReaderBuilder::new(Arc::new(schema)).build(cursor)?;
+
+    let mut batches = Vec::new();
+    while let Some(batch) = reader.next() {
+        batches.push(batch?);
+    }
+
+    arrow_to_ipc(&batches)
+}
+
+pub(crate) fn dataset_status(df: &Arc<DataFusion>, ds: &Arc<Dataset>) -> ComponentStatus {
+    df.table_status(&datafusion::sql::TableReference::parse_str(
+        ds.name.as_str(),
+    ))
+}
+
+/// Executes `sql` against the runtime's `DataFusion` context and serializes the resulting
+/// `RecordBatch`es in the negotiated `format`, mapping any query failure to the appropriate
+/// client/server status code via [`ApiError`].
+///
+/// `IpcStream` and `NdJson` write each batch to the client as it is produced, bounding server
+/// memory to a single batch. The other formats still buffer the full result set, since CSV/plain
+/// rendering needs all rows to compute column widths/valid framing up front.
+///
+/// `permit` is the caller's [`ConcurrencyLimiter`] slot for this query. For the buffered formats it
+/// is dropped once this function has collected the full result, same as before; for the streaming
+/// formats the actual DataFusion execution doesn't happen until the response body is drained by
+/// axum, well after this function returns, so `permit` is moved into the stream itself and held
+/// until the stream is fully drained or dropped -- otherwise a client could hold open far more
+/// concurrent streaming executions than `max_concurrent_queries` allows.
+pub(crate) async fn sql_to_http_response(
+    df: Arc<DataFusion>,
+    sql: &str,
+    format: ArrowFormat,
+    permit: LimiterPermit,
+) -> Result<Response, ApiError> {
+    let mut stream = df.query_stream(sql).await?;
+
+    match format {
+        ArrowFormat::IpcStream => Ok(ipc_stream_response(stream, permit)),
+        ArrowFormat::NdJson => Ok(ndjson_stream_response(stream, permit)),
+        buffered => {
+            let mut batches = Vec::new();
+            while let Some(batch) = stream.next().await {
+                batches.push(batch?);
+            }
+            drop(permit);
+
+            let body: Vec<u8> = match buffered {
+                ArrowFormat::Json => arrow_to_json(&batches)?.into_bytes(),
+                ArrowFormat::Csv => arrow_to_csv(&batches)?.into_bytes(),
+                ArrowFormat::Plain => arrow_to_plain(&batches)?.into_bytes(),
+                ArrowFormat::Parquet => arrow_to_parquet(&batches)?,
+                ArrowFormat::IpcStream | ArrowFormat::NdJson => unreachable!(),
+            };
+
+            Ok((
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, buffered.content_type())],
+                body,
+            )
+                .into_response())
+        }
+    }
+}
+
+/// A `std::io::Write` that accumulates bytes in a shared buffer instead of writing them anywhere
+/// directly, letting [`ipc_stream_response`] drain what a synchronous Arrow writer produced after
+/// each batch and yield it as its own chunk.
+#[derive(Clone, Default)]
+struct SharedBufWriter(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl SharedBufWriter {
+    /// Takes whatever bytes have accumulated since the last drain, leaving the buffer empty.
+    fn drain(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(std::sync::PoisonError::into_inner))
+    }
+}
+
+impl std::io::Write for SharedBufWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Streams `stream`'s batches to the client as an Arrow IPC stream (`application/vnd.apache.arrow.stream`),
+/// writing each batch as it arrives instead of materializing the full result set first. Yields
+/// directly from the same task that polls `stream` (no spawned producer racing ahead of the
+/// client), so a slow or stalled client backpressures the query itself, bounding server memory to
+/// a single batch.
+fn ipc_stream_response(
+    mut stream: datafusion::execution::SendableRecordBatchStream,
+    permit: LimiterPermit,
+) -> Response {
+    let schema = stream.schema();
+
+    let body_stream = async_stream::stream! {
+        // Held for the lifetime of this stream, not just until the `Response` is built -- the
+        // query hasn't actually run a single batch yet at that point.
+        let _permit = permit;
+        let buf = SharedBufWriter::default();
+        let mut writer = match arrow_ipc::writer::StreamWriter::try_new(buf.clone(), &schema) {
+            Ok(writer) => writer,
+            Err(e) => {
+                tracing::error!("Failed to start Arrow IPC stream: {e}");
+                yield Err(std::io::Error::other(e.to_string()));
+                return;
+            }
+        };
+
+        while let Some(batch) = stream.next().await {
+            match batch {
+                Ok(batch) => {
+                    if let Err(e) = writer.write(&batch) {
+                        tracing::error!("Failed to write Arrow IPC batch: {e}");
+                        yield Err(std::io::Error::other(e.to_string()));
+                        return;
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Query failed mid-stream: {e}");
+                    yield Err(std::io::Error::other(e.to_string()));
+                    return;
+                }
+            }
+
+            let chunk = buf.drain();
+            if !chunk.is_empty() {
+                yield Ok::<_, std::io::Error>(axum::body::Bytes::from(chunk));
+            }
+        }
+
+        if let Err(e) = writer.finish() {
+            tracing::error!("Failed to finish Arrow IPC stream: {e}");
+            yield Err(std::io::Error::other(e.to_string()));
+            return;
+        }
+
+        let chunk = buf.drain();
+        if !chunk.is_empty() {
+            yield Ok::<_, std::io::Error>(axum::body::Bytes::from(chunk));
+        }
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, ArrowFormat::IpcStream.content_type())
+        .header(header::TRANSFER_ENCODING, "chunked")
+        .body(Body::from_stream(body_stream))
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to build Arrow IPC stream response: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+}
+
+/// Streams `stream`'s batches to the client as newline-delimited JSON, one JSON object per row,
+/// flushing each batch as it arrives instead of materializing the full result set first.
+fn ndjson_stream_response(
+    mut stream: datafusion::execution::SendableRecordBatchStream,
+    permit: LimiterPermit,
+) -> Response {
+    let body_stream = async_stream::stream! {
+        // Held for the lifetime of this stream, not just until the `Response` is built -- the
+        // query hasn't actually run a single batch yet at that point.
+        let _permit = permit;
+        while let Some(batch) = stream.next().await {
+            match batch {
+                Ok(batch) => match arrow_to_ndjson(&[batch]) {
+                    Ok(chunk) => yield Ok::<_, std::io::Error>(axum::body::Bytes::from(chunk)),
+                    Err(e) => {
+                        yield Err(std::io::Error::other(e.to_string()));
+                        return;
+                    }
+                },
+                Err(e) => {
+                    yield Err(std::io::Error::other(e.to_string()));
+                    return;
+                }
+            }
+        }
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, ArrowFormat::NdJson.content_type())
+        .header(header::TRANSFER_ENCODING, "chunked")
+        .body(Body::from_stream(body_stream))
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to build NDJSON stream response: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+}
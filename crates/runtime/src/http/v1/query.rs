@@ -17,29 +17,58 @@ use std::sync::Arc;
 
 use axum::{
     body::Bytes,
-    http::StatusCode,
     response::{IntoResponse, Response},
     Extension,
 };
 use axum_extra::TypedHeader;
 use headers_accept::Accept;
 
-use crate::datafusion::DataFusion;
+use crate::{datafusion::DataFusion, http::limits::ConcurrencyLimiter};
 
-use super::{sql_to_http_response, ArrowFormat};
+use super::{sql_to_http_response, ApiError, ArrowFormat};
 
 pub(crate) async fn post(
     Extension(df): Extension<Arc<DataFusion>>,
+    Extension(limiter): Extension<Arc<ConcurrencyLimiter>>,
     accept: Option<TypedHeader<Accept>>,
     body: Bytes,
 ) -> Response {
-    let query = match String::from_utf8(body.to_vec()) {
-        Ok(query) => query,
-        Err(e) => {
-            tracing::debug!("Error reading query: {e}");
-            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
-        }
+    let result = post_impl(df, &limiter, accept, body).await;
+
+    // Computed after `post_impl` has returned -- by then a buffered response's permit has already
+    // been dropped, while a streamed (Arrow IPC / NDJSON) response still holds its permit until
+    // the body is drained -- and attached regardless of outcome so 429/503/400/500 responses carry
+    // them too, not just 200s.
+    let rate_limit_headers = limiter.rate_limit_headers();
+    let mut response = match result {
+        Ok(response) => response,
+        Err(err) => err.into_response(),
     };
+    response.headers_mut().extend(rate_limit_headers);
+    response
+}
+
+async fn post_impl(
+    df: Arc<DataFusion>,
+    limiter: &ConcurrencyLimiter,
+    accept: Option<TypedHeader<Accept>>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let permit = limiter
+        .acquire()
+        .await
+        .map_err(|retry_after| ApiError::ServiceUnavailable { retry_after })?;
+
+    let query = String::from_utf8(body.to_vec())
+        .map_err(|e| ApiError::BadRequest(format!("Invalid UTF-8 in query body: {e}")))?;
 
-    sql_to_http_response(df, &query, ArrowFormat::from_accept_header(&accept)).await
+    // `sql_to_http_response` takes ownership of `permit` and holds it for as long as the response
+    // actually takes to execute/stream, not just until this function returns.
+    sql_to_http_response(
+        df,
+        &query,
+        ArrowFormat::from_accept_header(accept.as_ref()),
+        permit,
+    )
+    .await
 }
@@ -213,3 +213,76 @@ const AUTOGENERATED_LABELS: [&str; 11] = [
 fn filter_event_keys(event_key: &str) -> bool {
     !AUTOGENERATED_LABELS.contains(&event_key)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use opentelemetry::{
+        trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState},
+        InstrumentationLibrary, KeyValue, Value as OtelValue,
+    };
+    use opentelemetry_sdk::trace::{Event, SpanEvents, SpanLinks};
+
+    use super::*;
+
+    /// A minimal [`SpanData`] shaped like the one [`crate::tools::dispatch_tool_call`] produces
+    /// for its `tool_use` span: an `input` span attribute, plus a `captured_output` *event*
+    /// attribute (never a span attribute -- that's the bug this guards against).
+    fn tool_use_span(input: &str, captured_output: &str) -> SpanData {
+        let span_context = SpanContext::new(
+            TraceId::from_u128(1),
+            SpanId::from_u64(1),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+
+        SpanData {
+            span_context,
+            parent_span_id: SpanId::INVALID,
+            span_kind: SpanKind::Internal,
+            name: "tool_use".into(),
+            start_time: SystemTime::UNIX_EPOCH,
+            end_time: SystemTime::UNIX_EPOCH,
+            attributes: vec![KeyValue::new("input", input.to_string())],
+            dropped_attributes_count: 0,
+            events: SpanEvents {
+                events: vec![Event::new(
+                    "tool_use",
+                    SystemTime::UNIX_EPOCH,
+                    vec![KeyValue::new(
+                        "captured_output",
+                        OtelValue::String(captured_output.to_string().into()),
+                    )],
+                    0,
+                )]
+                .into(),
+                dropped_count: 0,
+            },
+            links: SpanLinks::default(),
+            status: Status::Unset,
+            instrumentation_lib: InstrumentationLibrary::default(),
+        }
+    }
+
+    /// Regression test for a bug where `captured_output` was recorded as a span attribute
+    /// instead of logged as an event, so [`TaskHistoryExporter::span_to_task_span`] -- which only
+    /// ever looks at events for this key -- silently dropped it.
+    #[test]
+    fn span_to_task_span_extracts_captured_output_from_event() {
+        let exporter = TaskHistoryExporter::new(
+            Arc::new(DataFusion::default()),
+            TaskHistoryCapturedOutput::Truncated,
+        );
+
+        let span = tool_use_span(r#"{"sql":"select 1"}"#, r#"{"rows":1}"#);
+        let task_span = exporter.span_to_task_span(span);
+
+        assert_eq!(
+            task_span.captured_output.as_deref(),
+            Some(r#"{"rows":1}"#),
+            "captured_output should be extracted from the span's event attributes"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+/// How long a [`ToolResultCache`]'s entries are expected to remain valid for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolCacheScope {
+    /// Cleared before each top-level [`super::run_tool_loop`] call -- memoizes repeated tool calls
+    /// within one multi-step agentic turn only.
+    PerRequest,
+    /// Kept for as long as the owning conversation/session lasts, memoizing repeated calls across
+    /// turns.
+    PerSession,
+}
+
+/// Configuration for a [`ToolResultCache`].
+#[derive(Debug, Clone)]
+pub struct ToolResultCacheConfig {
+    pub scope: ToolCacheScope,
+    /// How long a cached result remains valid before it is treated as expired. `None` means
+    /// entries never expire on time (they can still be evicted under `max_entries` pressure).
+    pub ttl: Option<Duration>,
+    /// The maximum number of distinct `(tool, args)` pairs to retain.
+    pub max_entries: NonZeroUsize,
+}
+
+impl Default for ToolResultCacheConfig {
+    fn default() -> Self {
+        Self {
+            scope: ToolCacheScope::PerRequest,
+            ttl: Some(Duration::from_secs(5 * 60)),
+            max_entries: NonZeroUsize::new(256).unwrap_or(NonZeroUsize::MIN),
+        }
+    }
+}
+
+struct CacheEntry {
+    value: Value,
+    cached_at: Instant,
+}
+
+impl CacheEntry {
+    fn is_expired(&self, ttl: Option<Duration>) -> bool {
+        ttl.is_some_and(|ttl| self.cached_at.elapsed() > ttl)
+    }
+}
+
+/// Memoizes [`super::SpiceModelTool::call`] results keyed on `(tool name, args string)`, so a model
+/// re-requesting the same lookup during a multi-step loop (or, with [`ToolCacheScope::PerSession`],
+/// across an entire conversation) gets the previously computed result instead of re-running a
+/// possibly expensive query. Callers are responsible for never putting a
+/// [`super::SpiceModelTool::is_mutating`] tool's result in here.
+pub struct ToolResultCache {
+    config: ToolResultCacheConfig,
+    entries: RwLock<LruCache<(String, String), CacheEntry>>,
+}
+
+impl ToolResultCache {
+    #[must_use]
+    pub fn new(config: ToolResultCacheConfig) -> Self {
+        Self {
+            entries: RwLock::new(LruCache::new(config.max_entries)),
+            config,
+        }
+    }
+
+    #[must_use]
+    pub fn scope(&self) -> ToolCacheScope {
+        self.config.scope
+    }
+
+    /// Returns the cached result for `(tool_name, args)`, if present and not expired.
+    pub async fn get(&self, tool_name: &str, args: &str) -> Option<Value> {
+        let mut entries = self.entries.write().await;
+        let key = (tool_name.to_string(), args.to_string());
+        let entry = entries.get(&key)?;
+        if entry.is_expired(self.config.ttl) {
+            entries.pop(&key);
+            return None;
+        }
+        Some(entry.value.clone())
+    }
+
+    /// Caches `value` under `(tool_name, args)`, evicting the least-recently-used entry if the
+    /// cache is already at `max_entries`.
+    pub async fn put(&self, tool_name: &str, args: &str, value: Value) {
+        let mut entries = self.entries.write().await;
+        entries.put(
+            (tool_name.to_string(), args.to_string()),
+            CacheEntry {
+                value,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops every cached entry. Called between top-level loop invocations for
+    /// [`ToolCacheScope::PerRequest`] caches so they don't leak results across unrelated requests.
+    pub async fn clear(&self) {
+        self.entries.write().await.clear();
+    }
+}
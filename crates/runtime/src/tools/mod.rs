@@ -18,20 +18,24 @@ use async_openai::{
     types::{
         ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessageArgs,
         ChatCompletionRequestMessage, ChatCompletionRequestToolMessageArgs, ChatCompletionToolType,
-        FunctionCall,
+        CreateChatCompletionRequest, CreateChatCompletionResponse, FinishReason, FunctionCall,
     },
 };
 use async_trait::async_trait;
 use builtin::get_builtin_tools;
-use options::SpiceToolsOptions;
+use self::cache::ToolResultCache;
+use llms::chat::Chat;
+use options::{ToolApprovalPolicy, ToolExecutionOptions};
 use schemars::{schema_for, JsonSchema};
 use serde::Serialize;
 use serde_json::Value;
 use std::sync::Arc;
+use tracing::Instrument;
 
 use crate::Runtime;
 
 pub mod builtin;
+pub mod cache;
 pub mod factory;
 pub mod options;
 
@@ -44,6 +48,12 @@ pub trait SpiceModelTool: Sync + Send {
         None
     }
     fn parameters(&self) -> Option<Value>;
+    /// Whether `call` has side effects (writes data, triggers an external action) as opposed to
+    /// being a read-only query. Mutating tools are subject to the runtime's
+    /// [`ToolApprovalPolicy`] instead of always being auto-run.
+    fn is_mutating(&self) -> bool {
+        false
+    }
     async fn call(
         &self,
         arg: &str,
@@ -51,25 +61,41 @@ pub trait SpiceModelTool: Sync + Send {
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>>;
 }
 
+/// How a failing [`SpiceModelTool::call`] is surfaced to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolCallErrorHandling {
+    /// Propagate the failure as an [`OpenAIError`], aborting whatever request triggered it.
+    Strict,
+    /// Serialize the failure into the tool-result message's content (as `{"error": "<message>"}`)
+    /// so the model sees it as a normal tool result and can retry with different arguments or
+    /// fall back to another tool, instead of the whole turn aborting.
+    Lenient,
+}
+
 /// Creates the messages that would be sent and received if a language model were to request the `tool`
 /// to be called (via an assistant message), with defined `arg`, and the response from running the
 /// tool (via a tool message) also as a message.
 ///
 /// Useful for constructing [`Vec<ChatCompletionRequestMessage>`], simulating a model already
 /// having requested specific tools.
+///
+/// # Errors
+///
+/// If `tool.call` fails, returns an [`OpenAIError`] when `error_handling` is
+/// [`ToolCallErrorHandling::Strict`]; with [`ToolCallErrorHandling::Lenient`] the failure is
+/// instead embedded in the returned tool message and this always succeeds.
 pub async fn create_tool_use_messages(
     rt: Arc<Runtime>,
     tool: &dyn SpiceModelTool,
     id: &str,
     params: impl serde::Serialize,
+    error_handling: ToolCallErrorHandling,
 ) -> Result<Vec<ChatCompletionRequestMessage>, OpenAIError> {
     let arg =
         serde_json::to_string(&params).map_err(|e| OpenAIError::InvalidArgument(e.to_string()))?;
 
-    let resp = tool
-        .call(arg.as_str(), rt)
-        .await
-        .map_err(|e| OpenAIError::InvalidArgument(e.to_string()))?;
+    let result = tool.call(arg.as_str(), Arc::clone(&rt)).await;
+    let content = tool_result_content(result, error_handling)?;
 
     Ok(vec![
         ChatCompletionRequestAssistantMessageArgs::default()
@@ -84,13 +110,294 @@ pub async fn create_tool_use_messages(
             .build()?
             .into(),
         ChatCompletionRequestToolMessageArgs::default()
-            .content(resp.to_string())
+            .content(content)
             .tool_call_id(id.to_string())
             .build()?
             .into(),
     ])
 }
 
+/// Turns a tool's call result into the string content of its tool-result message, honoring
+/// `error_handling` when the call failed.
+fn tool_result_content(
+    result: Result<Value, Box<dyn std::error::Error + Send + Sync>>,
+    error_handling: ToolCallErrorHandling,
+) -> Result<String, OpenAIError> {
+    match result {
+        Ok(value) => Ok(value.to_string()),
+        Err(e) => match error_handling {
+            ToolCallErrorHandling::Strict => Err(OpenAIError::InvalidArgument(e.to_string())),
+            ToolCallErrorHandling::Lenient => {
+                Ok(serde_json::json!({ "error": e.to_string() }).to_string())
+            }
+        },
+    }
+}
+
+/// The outcome of [`run_tool_loop`]: the full message transcript (the original request's messages
+/// plus every assistant tool-call / tool-result message appended along the way) and the final
+/// completion the model returned once it stopped requesting tools. If `max_steps` was hit, it is
+/// the completion of one extra request sent with the full transcript; any tool calls it requests
+/// are not dispatched.
+pub struct ToolLoopResult {
+    pub messages: Vec<ChatCompletionRequestMessage>,
+    pub response: CreateChatCompletionResponse,
+}
+
+/// Drives a `model` through repeated rounds of function calling: send `request`, and whenever the
+/// response's `finish_reason` is [`FinishReason::ToolCalls`], dispatch each requested tool call
+/// through the matching [`SpiceModelTool::call`], append the assistant tool-call message and the
+/// resulting tool messages to the running history, and re-send with that history. This continues
+/// until the model replies without requesting a tool, or `max_steps` rounds have been sent,
+/// whichever comes first -- the cap exists so a model stuck re-requesting the same tool can't spin
+/// the runtime forever.
+///
+/// A failing tool call is handled [`ToolCallErrorHandling::Lenient`]ly: its error is folded into
+/// the tool-result message instead of aborting the loop, so the model can see what went wrong and
+/// retry with corrected arguments or a different tool.
+///
+/// # Errors
+///
+/// Returns an error if the model request fails or a requested tool is not found among `tools`.
+pub async fn run_tool_loop(
+    rt: Arc<Runtime>,
+    model: &Arc<Box<dyn Chat>>,
+    request: CreateChatCompletionRequest,
+    tools: &[Arc<dyn SpiceModelTool>],
+    execution: &ToolExecutionOptions,
+    cache: Option<&ToolResultCache>,
+    max_steps: usize,
+) -> Result<ToolLoopResult, OpenAIError> {
+    if let Some(cache) = cache {
+        if cache.scope() == cache::ToolCacheScope::PerRequest {
+            cache.clear().await;
+        }
+    }
+    let mut messages = request.messages.clone();
+    let mut step_request = request;
+
+    for _ in 0..max_steps.max(1) {
+        step_request.messages.clone_from(&messages);
+        let response = model
+            .chat_request(step_request.clone())
+            .await
+            .map_err(|e| OpenAIError::InvalidArgument(e.to_string()))?;
+
+        let Some(choice) = response.choices.first() else {
+            return Ok(ToolLoopResult { messages, response });
+        };
+
+        if choice.finish_reason != Some(FinishReason::ToolCalls) {
+            return Ok(ToolLoopResult { messages, response });
+        }
+
+        let tool_calls = choice.message.tool_calls.clone().unwrap_or_default();
+
+        let mut assistant_message = ChatCompletionRequestAssistantMessageArgs::default();
+        if let Some(content) = &choice.message.content {
+            assistant_message.content(content.clone());
+        }
+        assistant_message.tool_calls(tool_calls.clone());
+        messages.push(assistant_message.build()?.into());
+
+        let result_messages = dispatch_tool_calls(
+            Arc::clone(&rt),
+            tools,
+            &tool_calls,
+            ToolCallErrorHandling::Lenient,
+            execution,
+            cache,
+        )
+        .await?;
+        messages.extend(result_messages);
+
+        if tool_calls.is_empty() {
+            return Ok(ToolLoopResult { messages, response });
+        }
+    }
+
+    // `max_steps` was hit while the model was still requesting tools; re-send once more so the
+    // caller gets a completion that reflects the full transcript rather than a bare tool-call turn.
+    step_request.messages.clone_from(&messages);
+    let response = model
+        .chat_request(step_request)
+        .await
+        .map_err(|e| OpenAIError::InvalidArgument(e.to_string()))?;
+    Ok(ToolLoopResult { messages, response })
+}
+
+/// Dispatches a whole turn's worth of `tool_calls`, running each contiguous run of independent,
+/// non-mutating calls concurrently (bounded by a semaphore sized from
+/// [`std::thread::available_parallelism`]) and each [`SpiceModelTool::is_mutating`] call on its
+/// own, one at a time. A pending non-mutating batch is always awaited in full before the next
+/// mutating call starts, so a mutating call can never race ahead of an earlier-ordered
+/// non-mutating one. Results are reassembled in the original `tool_calls` order regardless of
+/// which ones ran in parallel.
+async fn dispatch_tool_calls(
+    rt: Arc<Runtime>,
+    tools: &[Arc<dyn SpiceModelTool>],
+    tool_calls: &[ChatCompletionMessageToolCall],
+    error_handling: ToolCallErrorHandling,
+    execution: &ToolExecutionOptions,
+    cache: Option<&ToolResultCache>,
+) -> Result<Vec<ChatCompletionRequestMessage>, OpenAIError> {
+    let max_concurrency = std::thread::available_parallelism()
+        .map(std::num::NonZeroUsize::get)
+        .unwrap_or(1);
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(max_concurrency));
+
+    let mut results: Vec<Option<ChatCompletionRequestMessage>> = vec![None; tool_calls.len()];
+    let mut parallel = Vec::new();
+
+    for (idx, tool_call) in tool_calls.iter().enumerate() {
+        let is_mutating = tools
+            .iter()
+            .any(|t| t.name() == tool_call.function.name && t.is_mutating());
+
+        if is_mutating {
+            // Flush any pending non-mutating batch first, so a mutating call never starts
+            // before an earlier-ordered non-mutating call has actually run.
+            for (idx, message) in futures::future::join_all(std::mem::take(&mut parallel)).await {
+                results[idx] = Some(message?);
+            }
+
+            let message = dispatch_tool_call(
+                Arc::clone(&rt),
+                tools,
+                tool_call,
+                error_handling,
+                execution,
+                cache,
+            )
+            .await?;
+            results[idx] = Some(message);
+        } else {
+            let semaphore = Arc::clone(&semaphore);
+            let rt = Arc::clone(&rt);
+            parallel.push(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let message =
+                    dispatch_tool_call(rt, tools, tool_call, error_handling, execution, cache)
+                        .await;
+                (idx, message)
+            });
+        }
+    }
+
+    for (idx, message) in futures::future::join_all(parallel).await {
+        results[idx] = Some(message?);
+    }
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// Finds the tool named by `tool_call` among `tools`, invokes it with the call's arguments, and
+/// wraps the outcome in a [`ChatCompletionRequestToolMessage`] carrying the matching `tool_call_id`.
+///
+/// If the tool is [`SpiceModelTool::is_mutating`], `execution`'s [`ToolApprovalPolicy`] is checked
+/// before the call is made; a denial is surfaced the same way as a failed call. A non-mutating
+/// tool's result is served from (and saved to) `cache`, if one is given, so an identical
+/// `(name, args)` call made earlier in the loop/session doesn't re-run.
+///
+/// The call runs inside its own `tool_use` span -- named after the tool via the `otel.name`
+/// override, carrying an `input` attribute and logging a `captured_output` event once the result
+/// is known -- so [`TaskHistoryExporter`] records it as a child task of whatever chat-completion
+/// span is current, making a multi-step function-calling chain reconstructable from task history.
+///
+/// [`TaskHistoryExporter`]: crate::task_history::otel_exporter::TaskHistoryExporter
+async fn dispatch_tool_call(
+    rt: Arc<Runtime>,
+    tools: &[Arc<dyn SpiceModelTool>],
+    tool_call: &ChatCompletionMessageToolCall,
+    error_handling: ToolCallErrorHandling,
+    execution: &ToolExecutionOptions,
+    cache: Option<&ToolResultCache>,
+) -> Result<ChatCompletionRequestMessage, OpenAIError> {
+    let tool = tools
+        .iter()
+        .find(|t| t.name() == tool_call.function.name)
+        .ok_or_else(|| {
+            OpenAIError::InvalidArgument(format!(
+                "model requested unknown tool '{}'",
+                tool_call.function.name
+            ))
+        })?;
+
+    let cacheable = cache.filter(|_| !tool.is_mutating());
+    let args = tool_call.function.arguments.as_str();
+
+    let span = tracing::info_span!(
+        "tool_use",
+        "otel.name" = %tool.name(),
+        input = %args,
+    );
+
+    let tool_for_call = Arc::clone(tool);
+    let result = async move {
+        if let Some(cached) = match cacheable {
+            Some(cache) => cache.get(tool_for_call.name(), args).await,
+            None => None,
+        } {
+            Ok(cached)
+        } else {
+            match check_tool_approval(tool_for_call.as_ref(), tool_call, execution).await {
+                Ok(()) => {
+                    let result = tool_for_call.call(args, rt).await;
+                    if let (Some(cache), Ok(value)) = (cacheable, &result) {
+                        cache.put(tool_for_call.name(), args, value.clone()).await;
+                    }
+                    result
+                }
+                Err(e) => Err(e),
+            }
+        }
+    }
+    .instrument(span.clone())
+    .await;
+
+    let content = tool_result_content(result, error_handling)?;
+    tracing::info!(parent: &span, captured_output = %content);
+
+    Ok(ChatCompletionRequestToolMessageArgs::default()
+        .content(content)
+        .tool_call_id(tool_call.id.clone())
+        .build()?
+        .into())
+}
+
+/// Checks whether a mutating `tool` is allowed to run under `execution`'s [`ToolApprovalPolicy`].
+/// Read-only tools are always allowed. A [`ToolApprovalPolicy::Confirm`] call with no confirmation
+/// callback configured is denied, since there is nothing to approve it.
+async fn check_tool_approval(
+    tool: &dyn SpiceModelTool,
+    tool_call: &ChatCompletionMessageToolCall,
+    execution: &ToolExecutionOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !tool.is_mutating() {
+        return Ok(());
+    }
+
+    match execution.approval {
+        ToolApprovalPolicy::AutoRun => Ok(()),
+        ToolApprovalPolicy::Deny => Err(format!(
+            "mutating tool '{}' is denied by the runtime's tool approval policy",
+            tool.name()
+        )
+        .into()),
+        ToolApprovalPolicy::Confirm => {
+            let approved = match &execution.confirm {
+                Some(confirm) => confirm(tool.name(), &tool_call.function.arguments).await,
+                None => false,
+            };
+            if approved {
+                Ok(())
+            } else {
+                Err(format!("mutating tool '{}' call was not approved", tool.name()).into())
+            }
+        }
+    }
+}
+
 /// Construct a [`serde_json::Value`] from a [`JsonSchema`] type.
 fn parameters<T: JsonSchema + Serialize>() -> Option<Value> {
     match serde_json::to_value(schema_for!(T)) {
@@ -102,12 +409,18 @@ fn parameters<T: JsonSchema + Serialize>() -> Option<Value> {
     }
 }
 
+/// Resolves the tools available for a request from `execution.tools`, then -- if
+/// `execution.approval` is [`ToolApprovalPolicy::Deny`] -- drops any [`SpiceModelTool::is_mutating`]
+/// tool from the list entirely, so a model is never even offered a tool it isn't allowed to call.
 #[must_use]
-pub async fn get_tools(rt: Arc<Runtime>, opts: &SpiceToolsOptions) -> Vec<Arc<dyn SpiceModelTool>> {
-    match opts {
-        SpiceToolsOptions::Disabled => vec![],
-        SpiceToolsOptions::Auto => get_builtin_tools(),
-        SpiceToolsOptions::Specific(t) => {
+pub async fn get_tools(
+    rt: Arc<Runtime>,
+    execution: &ToolExecutionOptions,
+) -> Vec<Arc<dyn SpiceModelTool>> {
+    let tools = match &execution.tools {
+        options::SpiceToolsOptions::Disabled => vec![],
+        options::SpiceToolsOptions::Auto => get_builtin_tools(),
+        options::SpiceToolsOptions::Specific(t) => {
             let mut tools = vec![];
             let all_tools = rt.tools.read().await;
 
@@ -120,5 +433,153 @@ pub async fn get_tools(rt: Arc<Runtime>, opts: &SpiceToolsOptions) -> Vec<Arc<dy
             }
             tools
         }
+    };
+
+    if execution.approval == ToolApprovalPolicy::Deny {
+        tools.into_iter().filter(|t| !t.is_mutating()).collect()
+    } else {
+        tools
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use options::SpiceToolsOptions;
+
+    use super::*;
+
+    /// A [`SpiceModelTool`] that records when it starts and finishes into a shared order log,
+    /// optionally sleeping first -- lets tests assert on the relative ordering
+    /// [`dispatch_tool_calls`] produces for a batch that mixes mutating and non-mutating calls.
+    struct OrderTrackingTool {
+        name: &'static str,
+        mutating: bool,
+        delay: Duration,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl SpiceModelTool for OrderTrackingTool {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> Option<&str> {
+            None
+        }
+
+        fn parameters(&self) -> Option<Value> {
+            None
+        }
+
+        fn is_mutating(&self) -> bool {
+            self.mutating
+        }
+
+        async fn call(
+            &self,
+            _arg: &str,
+            _rt: Arc<Runtime>,
+        ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+            self.log
+                .lock()
+                .expect("log mutex poisoned")
+                .push(format!("{}:start", self.name));
+            if !self.delay.is_zero() {
+                tokio::time::sleep(self.delay).await;
+            }
+            self.log
+                .lock()
+                .expect("log mutex poisoned")
+                .push(format!("{}:end", self.name));
+            Ok(Value::String(self.name.to_string()))
+        }
+    }
+
+    fn tool_call(id: &str, name: &str) -> ChatCompletionMessageToolCall {
+        ChatCompletionMessageToolCall {
+            id: id.to_string(),
+            r#type: ChatCompletionToolType::Function,
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: "{}".to_string(),
+            },
+        }
+    }
+
+    /// Regression test for a bug where a mutating call could start running before an
+    /// earlier-ordered, still in-flight non-mutating call had finished. A batch ordered
+    /// `[slow_reader, writer]` must have `slow_reader` fully finish before `writer` starts, and
+    /// the returned messages must come back in the original `tool_calls` order regardless of
+    /// which ones ran concurrently.
+    #[tokio::test]
+    async fn dispatch_tool_calls_flushes_pending_batch_before_mutating_call() {
+        let log: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let rt = Arc::new(Runtime::builder().build().await);
+
+        let slow_reader: Arc<dyn SpiceModelTool> = Arc::new(OrderTrackingTool {
+            name: "slow_reader",
+            mutating: false,
+            delay: Duration::from_millis(50),
+            log: Arc::clone(&log),
+        });
+        let writer: Arc<dyn SpiceModelTool> = Arc::new(OrderTrackingTool {
+            name: "writer",
+            mutating: true,
+            delay: Duration::ZERO,
+            log: Arc::clone(&log),
+        });
+        let tools = vec![Arc::clone(&slow_reader), Arc::clone(&writer)];
+
+        let tool_calls = vec![
+            tool_call("call-1", "slow_reader"),
+            tool_call("call-2", "writer"),
+        ];
+
+        let execution =
+            ToolExecutionOptions::new(SpiceToolsOptions::Auto).with_approval(ToolApprovalPolicy::AutoRun);
+
+        let messages = dispatch_tool_calls(
+            rt,
+            &tools,
+            &tool_calls,
+            ToolCallErrorHandling::Strict,
+            &execution,
+            None,
+        )
+        .await
+        .expect("dispatch should succeed");
+
+        assert_eq!(messages.len(), 2, "one tool message per tool call, in order");
+        let tool_call_ids: Vec<&str> = messages
+            .iter()
+            .map(|message| match message {
+                ChatCompletionRequestMessage::Tool(tool_message) => {
+                    tool_message.tool_call_id.as_str()
+                }
+                _ => "",
+            })
+            .collect();
+        assert_eq!(
+            tool_call_ids,
+            ["call-1", "call-2"],
+            "tool messages should come back in the original tool_calls order"
+        );
+
+        let order = log.lock().expect("log mutex poisoned").clone();
+        let reader_end = order
+            .iter()
+            .position(|e| e == "slow_reader:end")
+            .expect("slow_reader should have finished");
+        let writer_start = order
+            .iter()
+            .position(|e| e == "writer:start")
+            .expect("writer should have started");
+        assert!(
+            reader_end < writer_start,
+            "mutating call started before the earlier-ordered non-mutating call finished: {order:?}"
+        );
     }
 }
@@ -0,0 +1,99 @@
+/*
+Copyright 2024 The Spice.ai OSS Authors
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+     https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+/// Which tools the runtime exposes to a model for a given chat/agentic request.
+#[derive(Debug, Clone, Default)]
+pub enum SpiceToolsOptions {
+    /// No tools are made available.
+    Disabled,
+    /// Every builtin tool is made available.
+    #[default]
+    Auto,
+    /// Only the named tools (matched against [`super::SpiceModelTool::name`]) are made available.
+    Specific(Vec<String>),
+}
+
+/// Governs whether a tool flagged [`super::SpiceModelTool::is_mutating`] is actually allowed to
+/// run, mirroring the distinction between read-only "query" functions and side-effecting
+/// "execute" functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToolApprovalPolicy {
+    /// Mutating tools run exactly like read-only ones, with no extra gate.
+    AutoRun,
+    /// Mutating tools are never offered to the model or run.
+    Deny,
+    /// Mutating tools are offered, but each call must be approved by the confirmation callback
+    /// before it runs. A call is treated as denied if no callback is configured.
+    #[default]
+    Confirm,
+}
+
+/// A caller-supplied hook asked to approve a single mutating tool call, given the tool's name and
+/// its JSON arguments. Returning `false` denies that specific call.
+pub type ToolConfirmationCallback =
+    Arc<dyn Fn(&str, &str) -> BoxFuture<'static, bool> + Send + Sync>;
+
+/// Which tools are exposed for a request, plus the policy applied to any of them flagged as
+/// mutating.
+#[derive(Clone)]
+pub struct ToolExecutionOptions {
+    pub tools: SpiceToolsOptions,
+    pub approval: ToolApprovalPolicy,
+    pub confirm: Option<ToolConfirmationCallback>,
+}
+
+impl Debug for ToolExecutionOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolExecutionOptions")
+            .field("tools", &self.tools)
+            .field("approval", &self.approval)
+            .field("confirm", &self.confirm.is_some())
+            .finish()
+    }
+}
+
+impl ToolExecutionOptions {
+    #[must_use]
+    pub fn new(tools: SpiceToolsOptions) -> Self {
+        Self {
+            tools,
+            approval: ToolApprovalPolicy::default(),
+            confirm: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_approval(mut self, approval: ToolApprovalPolicy) -> Self {
+        self.approval = approval;
+        self
+    }
+
+    #[must_use]
+    pub fn with_confirm(mut self, confirm: ToolConfirmationCallback) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+}
+
+impl From<SpiceToolsOptions> for ToolExecutionOptions {
+    fn from(tools: SpiceToolsOptions) -> Self {
+        Self::new(tools)
+    }
+}